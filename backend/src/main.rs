@@ -1,29 +1,35 @@
 use axum::{
-    extract::{Path, State, ws::{Message, WebSocket, WebSocketUpgrade}},
+    extract::{Path, Query, State, ws::{Message, WebSocket, WebSocketUpgrade}},
     response::{IntoResponse, Json},
     routing::{get, post},
     Router,
 };
-use std::{collections::HashMap, sync::{Arc, Mutex}};
+use std::{collections::HashMap, sync::Arc};
 use std::net::SocketAddr;
+use futures_util::{sink::SinkExt, stream::StreamExt};
 use serde::Deserialize;
 use serde_json::{json, Value};
+use tokio::sync::{broadcast, Mutex};
 use tower_http::cors::{Any, CorsLayer};
+use uuid::Uuid;
 
 mod models;
-use models::{Game, Player, Difficulty};
+use models::{BotMoveInfo, ClientMessage, ConnectionStatus, Difficulty, Game, GameRules, GameStatus, Player, ServerMessage};
 
 type GameStore = Arc<Mutex<HashMap<String, Game>>>;
+type ChannelStore = Arc<Mutex<HashMap<String, broadcast::Sender<String>>>>;
 
 #[derive(Clone)]
 struct AppState {
     games: GameStore,
+    channels: ChannelStore,
 }
 
 #[tokio::main]
 async fn main() {
     let state = AppState {
         games: Arc::new(Mutex::new(HashMap::new())),
+        channels: Arc::new(Mutex::new(HashMap::new())),
     };
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -32,125 +38,394 @@ async fn main() {
 
     let app = Router::new()
         .route("/", get(health_check))
+        .route("/games", get(lobby_handler))
         .route("/create_game", post(create_game_handler))
+        .route("/join_game/:game_id", post(join_game_handler))
+        .route("/replay/:game_id", get(replay_handler))
         .route("/ws/:game_id", get(ws_handler))
         .layer(cors)
         .with_state(state);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     println!("Battleship Backend listening on {}", addr);
-    
+
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
 
 async fn health_check() -> &'static str { "Battleship Server Running" }
 
+/// Returns the per-game broadcast channel, creating it if this is the first socket to touch the game.
+async fn get_or_create_channel(state: &AppState, game_id: &str) -> broadcast::Sender<String> {
+    let mut channels = state.channels.lock().await;
+    channels
+        .entry(game_id.to_string())
+        .or_insert_with(|| broadcast::channel(16).0)
+        .clone()
+}
+
+async fn lobby_handler(State(state): State<AppState>) -> Json<Value> {
+    let games = state.games.lock().await;
+    let channels = state.channels.lock().await;
+
+    let open: Vec<Value> = games
+        .values()
+        .filter(|g| g.status != GameStatus::Finished)
+        .map(|g| {
+            let players_connected = channels.get(&g.id).map(|tx| tx.receiver_count()).unwrap_or(0);
+            json!({
+                "game_id": g.id,
+                "status": g.status,
+                "players_connected": players_connected,
+                "has_opponent": g.player_2.is_some(),
+                "player_1_status": g.player_1.connection_status,
+                "player_2_status": g.player_2.as_ref().map(|p| &p.connection_status),
+            })
+        })
+        .collect();
+
+    Json(json!({ "games": open }))
+}
+
+#[derive(Deserialize)]
+struct RulesRequest {
+    board_size: Option<usize>,
+    fleet: Option<Vec<u8>>,
+}
+
 #[derive(Deserialize)]
 struct CreateGameRequest {
     difficulty: Option<String>,
+    // "bot" (default) seats a bot as player_2 immediately; "pvp" leaves the game open for /join_game.
+    mode: Option<String>,
+    // Omitted fields fall back to the classic 10x10 / [5,4,3,3,2] ruleset.
+    rules: Option<RulesRequest>,
 }
 
 async fn create_game_handler(
-    State(state): State<AppState>, 
+    State(state): State<AppState>,
     Json(payload): Json<CreateGameRequest>,
 ) -> Json<Value> {
-    // Defaulting to "Easy" as requested
-    let diff_str = payload.difficulty.unwrap_or_else(|| "Easy".to_string());
-    let difficulty = match diff_str.as_str() {
-        "Medium" => Difficulty::Medium,
-        "Hard" => Difficulty::Hard,
-        _ => Difficulty::Easy,
+    let rules = match payload.rules {
+        Some(r) => GameRules {
+            board_size: r.board_size.unwrap_or_else(|| GameRules::classic().board_size),
+            fleet: r.fleet.unwrap_or_else(|| GameRules::classic().fleet),
+        },
+        None => GameRules::classic(),
     };
+    if let Err(e) = rules.validate() {
+        return Json(json!({ "status": "error", "message": e }));
+    }
 
-    let player_1 = Player::new("User".to_string(), false, Difficulty::Easy);
-    let bot = Player::new("Bot".to_string(), true, difficulty);
-    
+    let player_1 = Player::new(Uuid::new_v4().to_string(), false, Difficulty::Easy, rules.clone());
+    let player_1_id = player_1.id.clone();
+    let player_1_token = player_1.reconnect_token.clone();
     let mut new_game = Game::new(player_1);
-    let _ = new_game.join_game(bot);
-    
+
+    let mode = payload.mode.unwrap_or_else(|| "bot".to_string());
+    if mode != "pvp" {
+        let diff_str = payload.difficulty.unwrap_or_else(|| "Easy".to_string());
+        let difficulty = match diff_str.as_str() {
+            "Medium" => Difficulty::Medium,
+            "Hard" => Difficulty::Hard,
+            _ => Difficulty::Easy,
+        };
+        let bot = Player::new("Bot".to_string(), true, difficulty, rules.clone());
+        let _ = new_game.join_game(bot);
+    }
+
     let game_id = new_game.id.clone();
     {
-        let mut games = state.games.lock().unwrap();
+        let mut games = state.games.lock().await;
         games.insert(game_id.clone(), new_game);
     }
 
-    Json(json!({ "status": "created", "game_id": game_id }))
+    Json(json!({
+        "status": "created",
+        "game_id": game_id,
+        "player_id": player_1_id,
+        "reconnect_token": player_1_token,
+    }))
+}
+
+async fn join_game_handler(
+    State(state): State<AppState>,
+    Path(game_id): Path<String>,
+) -> Json<Value> {
+    let existing_rules = {
+        let games = state.games.lock().await;
+        games.get(&game_id).map(|game| game.rules.clone())
+    };
+    let Some(rules) = existing_rules else {
+        return Json(json!({ "status": "error", "message": "game not found".to_string() }));
+    };
+
+    let player_2 = Player::new(Uuid::new_v4().to_string(), false, Difficulty::Easy, rules);
+    let player_2_id = player_2.id.clone();
+    let player_2_token = player_2.reconnect_token.clone();
+
+    let joined = {
+        let mut games = state.games.lock().await;
+        match games.get_mut(&game_id) {
+            Some(game) => game.join_game(player_2),
+            None => Err("game not found".to_string()),
+        }
+    };
+
+    match joined {
+        Ok(()) => {
+            let tx = get_or_create_channel(&state, &game_id).await;
+            let msg = ServerMessage::OpponentJoined { player_id: player_2_id.clone() };
+            let _ = tx.send(serde_json::to_string(&msg).unwrap());
+            Json(json!({
+                "status": "joined",
+                "game_id": game_id,
+                "player_id": player_2_id,
+                "reconnect_token": player_2_token,
+            }))
+        }
+        Err(e) => Json(json!({ "status": "error", "message": e })),
+    }
+}
+
+#[derive(Deserialize)]
+struct WsQuery {
+    token: String,
+}
+
+/// Returns the full shot history for a finished game so a front-end visualizer can step through
+/// the match turn by turn, plus both final boards and the winner. Requires one of the two
+/// players' reconnect tokens (same as `/ws`), and only serves boards once the game is over —
+/// otherwise this would leak a live opponent's ship layout mid-match.
+async fn replay_handler(
+    State(state): State<AppState>,
+    Path(game_id): Path<String>,
+    Query(query): Query<WsQuery>,
+) -> Json<Value> {
+    let games = state.games.lock().await;
+    let Some(game) = games.get(&game_id) else {
+        return Json(json!({ "status": "error", "message": "game not found" }));
+    };
+
+    let is_participant = game.player_1.reconnect_token == query.token
+        || game.player_2.as_ref().map(|p| p.reconnect_token == query.token).unwrap_or(false);
+    if !is_participant {
+        return Json(json!({ "status": "error", "message": "invalid reconnect token" }));
+    }
+
+    if game.status != GameStatus::Finished {
+        return Json(json!({ "status": "error", "message": "game is not finished yet" }));
+    }
+
+    let timeline: Vec<Value> = game
+        .moves
+        .iter()
+        .map(|m| json!({ "turn": m.turn, "player_id": m.player_id, "result": m.result }))
+        .collect();
+
+    Json(json!({
+        "status": "ok",
+        "game_id": game.id,
+        "winner": game.winner,
+        "moves": game.moves,
+        "timeline": timeline,
+        "player_1_board": game.player_1.board,
+        "player_2_board": game.player_2.as_ref().map(|p| &p.board),
+    }))
 }
 
 async fn ws_handler(
-    ws: WebSocketUpgrade, 
-    Path(game_id): Path<String>, 
+    ws: WebSocketUpgrade,
+    Path(game_id): Path<String>,
+    Query(query): Query<WsQuery>,
     State(state): State<AppState>
 ) -> impl IntoResponse {
-    let exists = state.games.lock().unwrap().contains_key(&game_id);
-    if !exists { return "Game not found".into_response(); }
-    
-    ws.on_upgrade(move |socket| handle_game_socket(socket, game_id, state))
+    let my_id = {
+        let games = state.games.lock().await;
+        let Some(game) = games.get(&game_id) else { return "Game not found".into_response(); };
+        if game.player_1.reconnect_token == query.token {
+            Some(game.player_1.id.clone())
+        } else if game.player_2.as_ref().map(|p| p.reconnect_token == query.token).unwrap_or(false) {
+            game.player_2.as_ref().map(|p| p.id.clone())
+        } else {
+            None
+        }
+    };
+    let Some(my_id) = my_id else { return "invalid reconnect token".into_response(); };
+
+    ws.on_upgrade(move |socket| handle_game_socket(socket, game_id, my_id, state))
+}
+
+/// Marks `player_id`'s connection in `game_id` as `status` and broadcasts the change so the
+/// opponent sees e.g. "opponent reconnecting...".
+async fn set_connection_status(state: &AppState, game_id: &str, player_id: &str, status: ConnectionStatus) {
+    let mut games = state.games.lock().await;
+    if let Some(game) = games.get_mut(game_id) {
+        if game.player_1.id == player_id {
+            game.player_1.connection_status = status.clone();
+        } else if game.player_2.as_ref().map(|p| p.id == player_id).unwrap_or(false) {
+            game.player_2.as_mut().unwrap().connection_status = status.clone();
+        }
+    }
+    drop(games);
+
+    let tx = get_or_create_channel(state, game_id).await;
+    let msg = ServerMessage::ConnectionUpdate { player_id: player_id.to_string(), status };
+    let _ = tx.send(serde_json::to_string(&msg).unwrap());
 }
 
-async fn handle_game_socket(mut socket: WebSocket, game_id: String, state: AppState) {
-    let my_id = "User".to_string(); 
+async fn handle_game_socket(socket: WebSocket, game_id: String, my_id: String, state: AppState) {
+    let tx = get_or_create_channel(&state, &game_id).await;
+    let mut rx = tx.subscribe();
+    let (mut sender, mut receiver) = socket.split();
 
-    // 1. Initial Handshake: Send the user their own board layout
+    // 1. Initial Handshake: Send the player their own board layout
     let init_msg = {
-        let games = state.games.lock().unwrap();
-        games.get(&game_id).map(|g| json!({ "type": "init", "board": g.player_1.board }).to_string())
+        let games = state.games.lock().await;
+        games.get(&game_id).and_then(|g| {
+            if g.player_1.id == my_id {
+                Some(g.player_1.board.clone())
+            } else if g.player_2.as_ref().map(|p| p.id == my_id).unwrap_or(false) {
+                g.player_2.as_ref().map(|p| p.board.clone())
+            } else {
+                None
+            }
+        })
     };
-    if let Some(msg) = init_msg { let _ = socket.send(Message::Text(msg)).await; }
-    
-    // 2. Main Game Loop
-    while let Some(Ok(msg)) = socket.recv().await {
-        if let Message::Text(text) = msg {
-            // Parse coordinates from "row,col" format
-            let parts: Vec<&str> = text.split(',').collect();
-            if parts.len() != 2 { continue; }
-            
-            let r: usize = parts[0].parse().unwrap_or(0);
-            let c: usize = parts[1].parse().unwrap_or(0);
-            
-            let response = {
-                let mut games = state.games.lock().unwrap();
-                if let Some(game) = games.get_mut(&game_id) {
-                    
-                    // --- PHASE 1: User's Turn ---
-                    match game.make_move(my_id.clone(), (r, c)) {
-                        Ok((user_res, winner)) => {
-                            let mut bot_data = None;
-
-                            // --- PHASE 2: Bot's Turn (if User didn't just win) ---
-                            if winner.is_none() {
-                                if let Some(bot_player) = game.player_2.as_mut() {
-                                    let (bot_r, bot_c) = bot_player.get_bot_move();
-                                    
-                                    if let Ok((b_res, b_win)) = game.make_move("Bot".to_string(), (bot_r, bot_c)) {
-                                        // Update bot's AI state based on the result
-                                        if let Some(bp) = game.player_2.as_mut() {
-                                            bp.process_bot_move_result((bot_r, bot_c), b_res);
+    if let Some(board) = init_msg {
+        let msg = ServerMessage::Init { board };
+        let _ = sender.send(Message::Text(serde_json::to_string(&msg).unwrap())).await;
+    }
+    set_connection_status(&state, &game_id, &my_id, ConnectionStatus::Connected).await;
+
+    // 2. Main Game Loop: fan shot results out to both connected sockets via the broadcast channel
+    loop {
+        tokio::select! {
+            incoming = receiver.next() => {
+                let Some(Ok(Message::Text(text))) = incoming else { break; };
+
+                let client_msg: ClientMessage = match serde_json::from_str(&text) {
+                    Ok(m) => m,
+                    Err(_) => {
+                        let err = ServerMessage::Error { message: "unrecognized message".to_string() };
+                        let _ = sender.send(Message::Text(serde_json::to_string(&err).unwrap())).await;
+                        continue;
+                    }
+                };
+
+                match client_msg {
+                    ClientMessage::Fire { row, col } => {
+                        let mut games = state.games.lock().await;
+                        let Some(game) = games.get_mut(&game_id) else { continue; };
+
+                        if game.current_turn != my_id {
+                            drop(games);
+                            let err = ServerMessage::Error { message: "not your turn".to_string() };
+                            let _ = sender.send(Message::Text(serde_json::to_string(&err).unwrap())).await;
+                            continue;
+                        }
+
+                        match game.make_move(my_id.clone(), (row, col)) {
+                            Ok((fire_result, winner)) => {
+                                let mut bot_data = None;
+
+                                // If the other seat is a bot, it moves immediately in the same turn.
+                                // `bot_move` is computed into owned locals first so the `&mut Player`
+                                // borrow from `player_2` ends before `game.make_move` needs `game` again.
+                                if winner.is_none() {
+                                    let bot_move = game.player_2.as_mut()
+                                        .filter(|p| p.is_bot)
+                                        .map(|p| (p.id.clone(), p.get_bot_move()));
+                                    if let Some((bot_id, (bot_r, bot_c))) = bot_move {
+                                        if let Ok((b_res, b_win)) = game.make_move(bot_id, (bot_r, bot_c)) {
+                                            if let Some(bp) = game.player_2.as_mut() {
+                                                bp.process_bot_move_result((bot_r, bot_c), b_res.cell, b_res.sunk.clone());
+                                            }
+                                            bot_data = Some((bot_r, bot_c, b_res, b_win));
                                         }
-                                        bot_data = Some((bot_r, bot_c, b_res, b_win));
                                     }
                                 }
-                            }
-                            Some(json!({
-                                "status": "success",
-                                "turn_update": {
-                                    "user": { "row": r, "col": c, "result": user_res },
-                                    "bot": bot_data.as_ref().map(|(br, bc, bres, _)| {
-                                        json!({ "row": br, "col": bc, "result": bres })
+
+                                let current_turn = game.current_turn.clone();
+                                drop(games);
+
+                                let resp = ServerMessage::TurnUpdate {
+                                    player: my_id.clone(),
+                                    row, col,
+                                    result: fire_result.cell,
+                                    sunk: fire_result.sunk,
+                                    bot: bot_data.as_ref().map(|(br, bc, bres, _)| {
+                                        BotMoveInfo { row: *br, col: *bc, result: bres.cell, sunk: bres.sunk.clone() }
                                     }),
-                                    "winner": winner.or(bot_data.and_then(|(_,_,_,w)| w))
+                                    winner: winner.clone().or(bot_data.as_ref().and_then(|(_,_,_,w)| w.clone())),
+                                    current_turn,
+                                };
+                                let _ = tx.send(serde_json::to_string(&resp).unwrap());
+
+                                if let Some(winner) = winner.or(bot_data.and_then(|(_,_,_,w)| w)) {
+                                    let game_over = ServerMessage::GameOver { winner };
+                                    let _ = tx.send(serde_json::to_string(&game_over).unwrap());
                                 }
-                            }))
-                        },
-                        Err(e) => Some(json!({ "status": "error", "message": e }))
+                            }
+                            Err(e) => {
+                                drop(games);
+                                let err = ServerMessage::Error { message: e };
+                                let _ = sender.send(Message::Text(serde_json::to_string(&err).unwrap())).await;
+                            }
+                        }
+                    }
+                    ClientMessage::Chat { message } => {
+                        let chat = ServerMessage::Chat { from: my_id.clone(), message };
+                        let _ = tx.send(serde_json::to_string(&chat).unwrap());
                     }
-                } else { None }
-            };
+                    ClientMessage::Resign => {
+                        let mut games = state.games.lock().await;
+                        let Some(game) = games.get_mut(&game_id) else { continue; };
+                        let winner = if my_id == game.player_1.id {
+                            game.player_2.as_ref().map(|p| p.id.clone())
+                        } else {
+                            Some(game.player_1.id.clone())
+                        };
+                        if let Some(winner) = winner {
+                            game.status = GameStatus::Finished;
+                            game.winner = Some(winner.clone());
+                            drop(games);
+                            let resp = ServerMessage::GameOver { winner };
+                            let _ = tx.send(serde_json::to_string(&resp).unwrap());
+                        }
+                    }
+                    ClientMessage::PlaceShips { ships } => {
+                        let mut games = state.games.lock().await;
+                        let Some(game) = games.get_mut(&game_id) else { continue; };
+                        let result = game.submit_layout(&my_id, ships);
+                        let status = game.status.clone();
+                        drop(games);
 
-            if let Some(resp) = response {
-                let _ = socket.send(Message::Text(resp.to_string())).await;
+                        match result {
+                            Ok(()) => {
+                                let ack = ServerMessage::LayoutAccepted { status };
+                                let _ = sender.send(Message::Text(serde_json::to_string(&ack).unwrap())).await;
+                            }
+                            Err(e) => {
+                                let err = ServerMessage::Error { message: e };
+                                let _ = sender.send(Message::Text(serde_json::to_string(&err).unwrap())).await;
+                            }
+                        }
+                    }
+                    ClientMessage::Rematch => {
+                        let err = ServerMessage::Error { message: "rematch is not supported yet".to_string() };
+                        let _ = sender.send(Message::Text(serde_json::to_string(&err).unwrap())).await;
+                    }
+                }
+            }
+            Ok(broadcast_msg) = rx.recv() => {
+                if sender.send(Message::Text(broadcast_msg)).await.is_err() {
+                    break;
+                }
             }
+            else => break,
         }
     }
-}
\ No newline at end of file
+
+    set_connection_status(&state, &game_id, &my_id, ConnectionStatus::Reconnecting).await;
+}
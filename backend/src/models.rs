@@ -1,7 +1,60 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use rand::Rng;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Standard Battleship fleet: one battleship (5), one cruiser (4), two destroyers (3), one sub (2).
+const FLEET_SIZES: [u8; 5] = [5, 4, 3, 3, 2];
+const CLASSIC_BOARD_SIZE: usize = 10;
+
+/// Board dimensions and fleet composition for a match. Carried on both `Game` and `Player` so
+/// board allocation, bounds checks, and win detection all derive from one source of truth
+/// instead of the board size and fleet being hardcoded throughout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRules {
+    pub board_size: usize,
+    pub fleet: Vec<u8>,
+}
+
+// Upper bound on board_size: large enough for any reasonable custom ruleset, small enough that
+// `vec![vec![CellState::Empty; board_size]; board_size]` can't be used to exhaust memory.
+const MAX_BOARD_SIZE: usize = 30;
+
+impl GameRules {
+    pub fn classic() -> Self {
+        Self { board_size: CLASSIC_BOARD_SIZE, fleet: FLEET_SIZES.to_vec() }
+    }
+
+    pub fn total_health(&self) -> u8 {
+        self.fleet.iter().sum()
+    }
+
+    /// Rejects an empty or oversized board, an empty fleet, any ship longer than the board it's
+    /// meant to be placed on, and a fleet with more ship cells than the board has or than the
+    /// `u8` health total can count.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.board_size == 0 || self.board_size > MAX_BOARD_SIZE {
+            return Err(format!("board_size must be between 1 and {}", MAX_BOARD_SIZE));
+        }
+        if self.fleet.is_empty() {
+            return Err("fleet must not be empty".to_string());
+        }
+        if self.fleet.iter().any(|&len| len == 0 || len as usize > self.board_size) {
+            return Err("every fleet entry must be between 1 and board_size".to_string());
+        }
+        let total_cells: usize = self.fleet.iter().map(|&len| len as usize).sum();
+        if total_cells > self.board_size * self.board_size || total_cells > u8::MAX as usize {
+            return Err("fleet has more ship cells than the board can hold".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
 pub enum Direction {
@@ -26,18 +79,79 @@ pub enum Difficulty {
 pub struct BotState {
     pub difficulty: Difficulty,
     pub shots_fired: HashSet<(usize, usize)>,
+    // Every shot this bot has taken, with its result, so the Hard bot can reason about the
+    // whole board instead of just its own hits.
+    pub shot_results: HashMap<(usize, usize), CellState>,
     pub last_hit: Option<(usize, usize)>,
     pub target_queue: VecDeque<(usize, usize)>,
+    // Lengths of ships not yet confirmed sunk, consumed by the Hard bot's density search.
+    pub remaining_lengths: Vec<u8>,
+    // Hit cells already attributed to a sunk ship, so they stop counting as "outstanding" hits.
+    pub sunk_cells: HashSet<(usize, usize)>,
 }
 impl BotState {
-    pub fn new(difficulty: Difficulty) -> Self {
+    pub fn new(difficulty: Difficulty, fleet: &[u8]) -> Self {
         Self {
             difficulty,
             shots_fired: HashSet::new(),
+            shot_results: HashMap::new(),
             last_hit: None,
             target_queue: VecDeque::new(),
+            remaining_lengths: fleet.to_vec(),
+            sunk_cells: HashSet::new(),
+        }
+    }
+}
+
+/// For every still-afloat ship length and orientation, slides it across the board and counts a
+/// placement as valid if it stays in bounds and covers no known-miss or already-sunk cell. Each
+/// valid placement increments the density of every not-yet-fired cell it covers. While there are unsunk hits
+/// outstanding, placements that don't cover at least one of them are discarded, so the search
+/// traces along a wounded ship instead of hunting blind.
+fn compute_density(state: &BotState, board_size: usize) -> HashMap<(usize, usize), u32> {
+    let outstanding_hits: Vec<(usize, usize)> = state
+        .shot_results
+        .iter()
+        .filter(|(coord, &result)| result == CellState::Hit && !state.sunk_cells.contains(coord))
+        .map(|(&coord, _)| coord)
+        .collect();
+    let target_mode = !outstanding_hits.is_empty();
+
+    let mut density: HashMap<(usize, usize), u32> = HashMap::new();
+    for &len in &state.remaining_lengths {
+        let len = len as usize;
+        for dir in [Direction::Horizontal, Direction::Vertical] {
+            for r in 0..board_size {
+                for c in 0..board_size {
+                    let cells: Vec<(usize, usize)> = (0..len)
+                        .map(|i| match dir {
+                            Direction::Horizontal => (r, c + i),
+                            Direction::Vertical => (r + i, c),
+                        })
+                        .collect();
+                    if cells.iter().any(|&(rr, cc)| rr >= board_size || cc >= board_size) { continue; }
+                    if cells.iter().any(|coord| {
+                        state.shot_results.get(coord) == Some(&CellState::Miss) || state.sunk_cells.contains(coord)
+                    }) { continue; }
+                    if target_mode && !cells.iter().any(|coord| outstanding_hits.contains(coord)) { continue; }
+
+                    for &coord in &cells {
+                        if !state.shots_fired.contains(&coord) {
+                            *density.entry(coord).or_insert(0) += 1;
+                        }
+                    }
+                }
+            }
         }
     }
+    density
+}
+
+fn first_unfired_cell(state: &BotState, board_size: usize) -> (usize, usize) {
+    (0..board_size)
+        .flat_map(|r| (0..board_size).map(move |c| (r, c)))
+        .find(|coord| !state.shots_fired.contains(coord))
+        .unwrap_or((0, 0))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,74 +163,114 @@ pub struct Ship {
     pub dir: Direction,
 }
 
+/// Outcome of a single shot: the cell it landed on, and the ship it sank, if any.
+#[derive(Debug, Clone, Serialize)]
+pub struct FireResult {
+    pub cell: CellState,
+    pub sunk: Option<Ship>,
+}
+
+/// Mirrors the planet-wars lobby's player-status model: a human starts `Waiting` on a socket,
+/// becomes `Connected` once one is open, and falls back to `Reconnecting` if it drops mid-game.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ConnectionStatus {
+    Waiting,
+    Connected,
+    Reconnecting,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub id: String,
     pub is_bot: bool,
-    pub board: [[CellState; 10]; 10],
+    pub board: Vec<Vec<CellState>>,
     pub ships: Vec<Ship>,
     pub remaining_health: u8,
     pub bot_state: Option<BotState>,
+    // Opaque secret presented on the WebSocket to re-bind a dropped socket to this player.
+    // Never included in the lobby listing or sent to the opponent.
+    pub reconnect_token: String,
+    pub connection_status: ConnectionStatus,
+    pub rules: GameRules,
 }
 
 impl Player {
-    pub fn new(id: String, is_bot: bool, difficulty: Difficulty) -> Self {
+    pub fn new(id: String, is_bot: bool, difficulty: Difficulty, rules: GameRules) -> Self {
         let bot_state = if is_bot {
-            Some(BotState::new(difficulty)) 
+            Some(BotState::new(difficulty, &rules.fleet))
         } else {
             None
         };
-        let mut player = Self { 
-            id, 
-            is_bot, 
-            board: [[CellState::Empty; 10]; 10], 
+        // Bots have no socket to wait on, so they're considered connected from the start.
+        let connection_status = if is_bot { ConnectionStatus::Connected } else { ConnectionStatus::Waiting };
+        let board_size = rules.board_size;
+        let total_health = rules.total_health();
+        let mut player = Self {
+            id,
+            is_bot,
+            board: vec![vec![CellState::Empty; board_size]; board_size],
             ships: Vec::new(),
-            remaining_health: 17,
+            remaining_health: total_health,
             bot_state,
+            reconnect_token: Uuid::new_v4().to_string(),
+            connection_status,
+            rules,
         };
         player.place_random_ships();
         player
     }
     pub fn get_bot_move(&mut self) -> (usize, usize) {
-        if let Some(state) = &mut self.bot_state {
-            while let Some(target) = state.target_queue.pop_front() {
-                if !state.shots_fired.contains(&target) {
-                    state.shots_fired.insert(target);
-                    return target;
-                }
+        let board_size = self.rules.board_size;
+        let Some(state) = &mut self.bot_state else { return (0, 0); };
+
+        if state.difficulty == Difficulty::Hard {
+            let coord = compute_density(state, board_size)
+                .into_iter()
+                .max_by_key(|&(_, density)| density)
+                .map(|(coord, _)| coord)
+                .unwrap_or_else(|| first_unfired_cell(state, board_size));
+            state.shots_fired.insert(coord);
+            return coord;
+        }
+
+        while let Some(target) = state.target_queue.pop_front() {
+            if !state.shots_fired.contains(&target) {
+                state.shots_fired.insert(target);
+                return target;
             }
-            let mut rng = rand::thread_rng();
-            loop {
-                let r = rng.gen_range(0..10);
-                let c = rng.gen_range(0..10);
-
-                if !state.shots_fired.contains(&(r, c)) {
-                    match state.difficulty {
-                        Difficulty::Easy => {
-                            state.shots_fired.insert((r, c));
-                            return (r, c);
-                        },
-                        Difficulty::Medium => {
-                            state.shots_fired.insert((r, c));
-                            return (r, c);
-                        },
-                        Difficulty::Hard => {
-                            if (r + c) % 2 == 0 {
-                                state.shots_fired.insert((r, c));
-                                return (r, c);
-                            }
-                        }
-                    }
-                }
+        }
+        let mut rng = rand::thread_rng();
+        loop {
+            let r = rng.gen_range(0..board_size);
+            let c = rng.gen_range(0..board_size);
+
+            if !state.shots_fired.contains(&(r, c)) {
+                state.shots_fired.insert((r, c));
+                return (r, c);
             }
-        } else {
-            (0, 0)
         }
     }
 
-    pub fn process_bot_move_result(&mut self, coords: (usize, usize), result: CellState) {
+    pub fn process_bot_move_result(&mut self, coords: (usize, usize), result: CellState, sunk: Option<Ship>) {
+        let max_index = self.rules.board_size - 1;
         if let Some(state) = &mut self.bot_state {
-            if state.difficulty != Difficulty::Easy && result == CellState::Hit {
+            state.shot_results.insert(coords, result);
+
+            if let Some(ship) = sunk {
+                if let Some(pos) = state.remaining_lengths.iter().position(|&l| l == ship.len) {
+                    state.remaining_lengths.remove(pos);
+                }
+                let (sr, sc) = ship.coordinates;
+                for i in 0..ship.len as usize {
+                    let cell = match ship.dir {
+                        Direction::Horizontal => (sr, sc + i),
+                        Direction::Vertical => (sr + i, sc),
+                    };
+                    state.sunk_cells.insert(cell);
+                }
+            }
+
+            if state.difficulty == Difficulty::Medium && result == CellState::Hit {
                 let (r, c) = coords;
                 let mut is_horizontal = false;
                 let mut is_vertical = false;
@@ -135,10 +289,10 @@ impl Player {
                 }
                 state.last_hit = Some(coords);
                 let mut moves = Vec::new();
-                if r > 0 { moves.push((r - 1, c)); } 
-                if r < 9 { moves.push((r + 1, c)); } 
-                if c > 0 { moves.push((r, c - 1)); } 
-                if c < 9 { moves.push((r, c + 1)); } 
+                if r > 0 { moves.push((r - 1, c)); }
+                if r < max_index { moves.push((r + 1, c)); }
+                if c > 0 { moves.push((r, c - 1)); }
+                if c < max_index { moves.push((r, c + 1)); }
 
                 for m in moves {
                     if !state.shots_fired.contains(&m) {
@@ -152,14 +306,15 @@ impl Player {
     }
 
     pub fn place_random_ships(&mut self) {
-        let ship_sizes = [5, 4, 3, 3, 2];
         let mut rng = rand::thread_rng();
+        let board_size = self.rules.board_size;
+        let fleet = self.rules.fleet.clone();
 
-        for (i, &len) in ship_sizes.iter().enumerate() {
+        for (i, &len) in fleet.iter().enumerate() {
             loop {
                 let dir = if rng.gen_bool(0.5) { Direction::Horizontal } else { Direction::Vertical };
-                let row = rng.gen_range(0..10);
-                let col = rng.gen_range(0..10);
+                let row = rng.gen_range(0..board_size);
+                let col = rng.gen_range(0..board_size);
 
                 let temp_ship = Ship {
                     id: format!("ship_{}", i),
@@ -175,15 +330,16 @@ impl Player {
         }
     }
     pub fn place_ship(&mut self, ship: Ship) -> Result<(), String> {
+        let board_size = self.rules.board_size;
         let (start_row, start_col) = ship.coordinates;
         let len = ship.len as usize;
         for i in 0..len {
             let (r, c) = match ship.dir {
                 Direction::Horizontal => (start_row, start_col + i),
-                Direction::Vertical => (start_row + i, start_col), 
+                Direction::Vertical => (start_row + i, start_col),
             };
-            
-            if r >= 10 || c >= 10 {
+
+            if r >= board_size || c >= board_size {
                 return Err("Ship goes out of bounds.".to_string());
             }
             if self.board[r][c] != CellState::Empty {
@@ -201,79 +357,357 @@ impl Player {
         Ok(())
     }
 
-    pub fn receive_shot(&mut self, coord: (usize, usize)) -> Result<CellState, String> {
+    /// Replaces this player's fleet with a manually-submitted layout. Validates the fleet
+    /// matches the required sizes and that every ship fits in bounds without overlap before
+    /// committing anything, so a bad layout leaves the existing board untouched.
+    pub fn set_layout(&mut self, ships: Vec<Ship>) -> Result<(), String> {
+        let mut lens: Vec<u8> = ships.iter().map(|s| s.len).collect();
+        lens.sort_unstable();
+        let mut required_sorted = self.rules.fleet.clone();
+        required_sorted.sort_unstable();
+        if lens != required_sorted {
+            return Err(format!("fleet must be exactly {:?}", self.rules.fleet));
+        }
+
+        let board_size = self.rules.board_size;
+        let mut board = vec![vec![CellState::Empty; board_size]; board_size];
+        let mut placed = Vec::with_capacity(ships.len());
+        for (i, ship) in ships.into_iter().enumerate() {
+            let (start_row, start_col) = ship.coordinates;
+            let len = ship.len as usize;
+            for j in 0..len {
+                let (r, c) = match ship.dir {
+                    Direction::Horizontal => (start_row, start_col + j),
+                    Direction::Vertical => (start_row + j, start_col),
+                };
+                if r >= board_size || c >= board_size {
+                    return Err("ship goes out of bounds".to_string());
+                }
+                if board[r][c] != CellState::Empty {
+                    return Err(format!("collision at {},{}", r, c));
+                }
+                board[r][c] = CellState::Ship;
+            }
+            placed.push(Ship { id: format!("ship_{}", i), len: ship.len, hits: 0, coordinates: ship.coordinates, dir: ship.dir });
+        }
+
+        self.board = board;
+        self.ships = placed;
+        self.remaining_health = self.rules.total_health();
+        Ok(())
+    }
+
+    pub fn receive_shot(&mut self, coord: (usize, usize)) -> Result<FireResult, String> {
         let (r, c) = coord;
-        if r >= 10 || c >= 10 {
+        if r >= self.rules.board_size || c >= self.rules.board_size {
             return Err("shot out of bounds".to_string());
         }
         match self.board[r][c] {
             CellState::Empty => {
                 self.board[r][c] = CellState::Miss;
-                Ok(CellState::Miss)
+                Ok(FireResult { cell: CellState::Miss, sunk: None })
             }
             CellState::Ship => {
                 self.board[r][c] = CellState::Hit;
-                self.remaining_health -= 1; 
-                Ok(CellState::Hit)
+                self.remaining_health -= 1;
+                let sunk = self.ships.iter_mut()
+                    .find(|ship| ship_covers(ship, coord))
+                    .and_then(|ship| {
+                        ship.hits += 1;
+                        if ship.hits == ship.len { Some(ship.clone()) } else { None }
+                    });
+                Ok(FireResult { cell: CellState::Hit, sunk })
             }
             CellState::Hit | CellState::Miss => {
                 Err("already fired here!".to_string())
             }
-        }   
+        }
     }
 }
 
+fn ship_covers(ship: &Ship, coord: (usize, usize)) -> bool {
+    let (sr, sc) = ship.coordinates;
+    (0..ship.len as usize).any(|i| {
+        match ship.dir {
+            Direction::Horizontal => (sr, sc + i) == coord,
+            Direction::Vertical => (sr + i, sc) == coord,
+        }
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum GameStatus {
-    Waiting,   
-    Playing,    
-    Finished,    
+    Waiting,
+    Placing,
+    Playing,
+    Finished,
+}
+
+/// Tagged inbound WebSocket frames. Replaces the old "row,col" text protocol.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    Fire { row: usize, col: usize },
+    PlaceShips { ships: Vec<Ship> },
+    Rematch,
+    Chat { message: String },
+    Resign,
+}
+
+/// A single bot shot, reported alongside the human's move in a `TurnUpdate`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BotMoveInfo {
+    pub row: usize,
+    pub col: usize,
+    pub result: CellState,
+    pub sunk: Option<Ship>,
+}
+
+/// Tagged outbound WebSocket frames. Replaces the old ad-hoc `json!({...})` blobs.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+    Init { board: Vec<Vec<CellState>> },
+    TurnUpdate {
+        player: String,
+        row: usize,
+        col: usize,
+        result: CellState,
+        sunk: Option<Ship>,
+        bot: Option<BotMoveInfo>,
+        winner: Option<String>,
+        current_turn: String,
+    },
+    Error { message: String },
+    GameOver { winner: String },
+    OpponentJoined { player_id: String },
+    Chat { from: String, message: String },
+    LayoutAccepted { status: GameStatus },
+    ConnectionUpdate { player_id: String, status: ConnectionStatus },
+}
+/// One fired shot, recorded in order for `GET /replay/:game_id` to reconstruct the match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveRecord {
+    pub turn: usize,
+    pub player_id: String,
+    pub row: usize,
+    pub col: usize,
+    pub result: CellState,
+    pub sunk_ship_id: Option<String>,
+    pub timestamp_ms: u128,
 }
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Game {
     pub id: String,
     pub status: GameStatus,
     pub player_1: Player,
-    pub player_2: Option<Player>, 
-    pub current_turn: String,      
-    pub winner: Option<String>,   
+    pub player_2: Option<Player>,
+    pub current_turn: String,
+    pub winner: Option<String>,
+    // Ids of players who have submitted a valid manual layout during `GameStatus::Placing`.
+    pub ready_players: HashSet<String>,
+    pub rules: GameRules,
+    pub moves: Vec<MoveRecord>,
 }
 
 impl Game {
     pub fn new(player_1: Player) -> Self {
         let first_turn = player_1.id.clone();
-        Self { 
-            id: Uuid::new_v4().to_string(), 
-            status: GameStatus::Waiting, 
-            player_1, 
-            player_2: None, 
-            current_turn: first_turn, 
-            winner: None 
+        let rules = player_1.rules.clone();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            status: GameStatus::Waiting,
+            player_1,
+            player_2: None,
+            current_turn: first_turn,
+            winner: None,
+            ready_players: HashSet::new(),
+            rules,
+            moves: Vec::new(),
         }
     }
 
     pub fn join_game(&mut self, player_2: Player) -> Result<(), String> {
         if self.player_2.is_none() {
+            // Bots arrive with a random layout already placed and are always ready to play;
+            // a second human needs a placement phase before shots can be fired.
+            self.status = if player_2.is_bot { GameStatus::Playing } else { GameStatus::Placing };
             self.player_2 = Some(player_2);
-            self.status = GameStatus::Playing;
             Ok(())
         } else {
             Err("game full".to_string())
         }
     }
-    pub fn make_move(&mut self, player_id: String, target: (usize, usize)) -> Result<(CellState, Option<String>), String> {
+
+    /// Submits a manual fleet layout for `player_id` while the game is in `GameStatus::Placing`.
+    /// Once every human player has a valid layout, the game transitions to `Playing`.
+    pub fn submit_layout(&mut self, player_id: &str, ships: Vec<Ship>) -> Result<(), String> {
+        if self.status != GameStatus::Placing {
+            return Err("layout can only be submitted while the game is in placement".to_string());
+        }
+
+        let player = if player_id == self.player_1.id {
+            &mut self.player_1
+        } else if self.player_2.as_ref().map(|p| p.id == player_id).unwrap_or(false) {
+            self.player_2.as_mut().unwrap()
+        } else {
+            return Err("unknown player".to_string());
+        };
+
+        player.set_layout(ships)?;
+        self.ready_players.insert(player_id.to_string());
+
+        let all_ready = !self.player_1.is_bot && self.ready_players.contains(&self.player_1.id)
+            && self.player_2.as_ref().map(|p| p.is_bot || self.ready_players.contains(&p.id)).unwrap_or(false);
+        if all_ready {
+            self.status = GameStatus::Playing;
+        }
+        Ok(())
+    }
+    pub fn make_move(&mut self, player_id: String, target: (usize, usize)) -> Result<(FireResult, Option<String>), String> {
+        if self.status != GameStatus::Playing {
+            return Err("game is not in progress".to_string());
+        }
         let opponent = if player_id == self.player_1.id {
              self.player_2.as_mut().ok_or("Player 2 missing")?
         } else {
              &mut self.player_1
         };
         let result = opponent.receive_shot(target)?;
-        let hits_made = 17 - opponent.remaining_health;
-        if hits_made >= 7 {
+
+        self.moves.push(MoveRecord {
+            turn: self.moves.len() + 1,
+            player_id: player_id.clone(),
+            row: target.0,
+            col: target.1,
+            result: result.cell,
+            sunk_ship_id: result.sunk.as_ref().map(|s| s.id.clone()),
+            timestamp_ms: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or(0),
+        });
+
+        if opponent.remaining_health == 0 {
             self.status = GameStatus::Finished;
             self.winner = Some(player_id.clone());
             return Ok((result, Some(player_id)));
         }
+        self.current_turn = if player_id == self.player_1.id {
+            self.player_2.as_ref().map(|p| p.id.clone()).unwrap_or(player_id)
+        } else {
+            self.player_1.id.clone()
+        };
         Ok((result, None))
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_rules() -> GameRules {
+        GameRules { board_size: 5, fleet: vec![2, 3] }
+    }
+
+    fn ship(id: &str, len: u8, coordinates: (usize, usize), dir: Direction) -> Ship {
+        Ship { id: id.to_string(), len, hits: 0, coordinates, dir }
+    }
+
+    #[test]
+    fn set_layout_rejects_wrong_fleet() {
+        let mut player = Player::new("p1".to_string(), false, Difficulty::Easy, small_rules());
+        let ships = vec![ship("a", 2, (0, 0), Direction::Horizontal)];
+        assert!(player.set_layout(ships).is_err());
+    }
+
+    #[test]
+    fn set_layout_rejects_out_of_bounds() {
+        let mut player = Player::new("p1".to_string(), false, Difficulty::Easy, small_rules());
+        let ships = vec![
+            ship("a", 2, (0, 4), Direction::Horizontal),
+            ship("b", 3, (1, 0), Direction::Horizontal),
+        ];
+        assert!(player.set_layout(ships).is_err());
+    }
+
+    #[test]
+    fn set_layout_rejects_overlap() {
+        let mut player = Player::new("p1".to_string(), false, Difficulty::Easy, small_rules());
+        let ships = vec![
+            ship("a", 2, (0, 0), Direction::Horizontal),
+            ship("b", 3, (0, 0), Direction::Vertical),
+        ];
+        assert!(player.set_layout(ships).is_err());
+    }
+
+    #[test]
+    fn set_layout_accepts_valid_layout() {
+        let mut player = Player::new("p1".to_string(), false, Difficulty::Easy, small_rules());
+        let ships = vec![
+            ship("a", 2, (0, 0), Direction::Horizontal),
+            ship("b", 3, (2, 0), Direction::Vertical),
+        ];
+        assert!(player.set_layout(ships).is_ok());
+        assert_eq!(player.ships.len(), 2);
+        assert_eq!(player.remaining_health, small_rules().total_health());
+        assert_eq!(player.board[0][0], CellState::Ship);
+        assert_eq!(player.board[0][1], CellState::Ship);
+        assert_eq!(player.board[2][0], CellState::Ship);
+    }
+
+    #[test]
+    fn compute_density_hunt_mode_skips_fired_and_missed_cells() {
+        let mut state = BotState::new(Difficulty::Hard, &[3]);
+        // Rule out every row but row 2 so the only placements for a length-3 ship land there.
+        for r in 0..5usize {
+            if r != 2 {
+                for c in 0..5usize {
+                    state.shot_results.insert((r, c), CellState::Miss);
+                }
+            }
+        }
+        // (2,0) lies inside valid row-2 placements but has already been fired at.
+        state.shots_fired.insert((2, 0));
+        let density = compute_density(&state, 5);
+        assert!(density.keys().all(|&(r, _)| r == 2));
+        assert!(!density.contains_key(&(2, 0)));
+        assert!(!density.is_empty());
+    }
+
+    #[test]
+    fn compute_density_target_mode_traces_outstanding_hit() {
+        let mut state = BotState::new(Difficulty::Hard, &[2]);
+        state.shot_results.insert((2, 2), CellState::Hit);
+        let density = compute_density(&state, 5);
+        // Every surviving placement of the length-2 ship must cover the outstanding hit at (2,2).
+        assert!(density.contains_key(&(1, 2)) || density.contains_key(&(3, 2)) ||
+                density.contains_key(&(2, 1)) || density.contains_key(&(2, 3)));
+        // A cell two rows away from the hit can't be covered by any length-2 placement through it.
+        assert!(!density.contains_key(&(0, 0)));
+    }
+
+    #[test]
+    fn process_bot_move_result_removes_sunk_length_from_remaining() {
+        let mut player = Player::new("bot".to_string(), true, Difficulty::Hard, GameRules { board_size: 5, fleet: vec![2, 3] });
+        let sunk_ship = ship("ship_0", 2, (0, 0), Direction::Horizontal);
+        player.process_bot_move_result((0, 1), CellState::Hit, Some(sunk_ship));
+
+        let state = player.bot_state.as_ref().unwrap();
+        assert!(!state.remaining_lengths.contains(&2));
+        assert!(state.remaining_lengths.contains(&3));
+        assert!(state.sunk_cells.contains(&(0, 0)));
+        assert!(state.sunk_cells.contains(&(0, 1)));
+    }
+
+    #[test]
+    fn get_bot_move_hard_never_refires_same_cell() {
+        let mut player = Player::new("bot".to_string(), true, Difficulty::Hard, small_rules());
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..small_rules().board_size.pow(2) {
+            let coord = player.get_bot_move();
+            assert!(seen.insert(coord), "bot fired on the same cell twice: {:?}", coord);
+        }
+    }
 }
\ No newline at end of file